@@ -44,18 +44,42 @@ pub struct PostgresInfo {
     // request
     pub context: String,
     pub req_type: u8,
+    // leading SQL command in readable form (SELECT/INSERT/...), surfaced as the
+    // request type instead of the raw protocol byte.
+    pub req_command: String,
+    // statement or portal name carried by the extended-query flow ('P'/'B'/'D'/'E'),
+    // shown as the resource when a request carries no SQL; session_id() does not
+    // use it.
+    pub statement: String,
+    // database and user extracted from the StartupMessage key/value list.
+    pub database: String,
+    pub user: String,
 
     // response
     pub resp_type: u8,
     pub response_code: u8,
+    // numeric SQLSTATE class (first two characters) when they are digits, e.g.
+    // 23 for an integrity-constraint violation; None for classes containing a
+    // letter (0A, HV, P0, XX, ...). The full code is always kept in `sqlstate`.
     pub error_code: Option<i32>,
+    // the full five-character SQLSTATE as received, letters included.
+    pub sqlstate: String,
     pub affected_rows: u64,
     pub error_message: String,
+    // human-readable SQLSTATE class derived from the 'C' field, e.g.
+    // "integrity constraint violation", used to group errors on dashboards.
+    pub error_class: String,
     pub status: L7ResponseStatus,
 }
 
 impl L7ProtocolInfoInterface for PostgresInfo {
     fn session_id(&self) -> Option<u32> {
+        // The statement/portal name only appears on the request path: no server
+        // reply (CommandComplete/ErrorResponse/DataRow) carries it, so hashing it
+        // would put a request in a different session bucket than its response and
+        // they would never merge. Correlation is therefore left to the framework's
+        // per-flow request/response pairing, which handles the extended-query flow
+        // the same way it handles simple queries.
         return None;
     }
 
@@ -71,12 +95,25 @@ impl L7ProtocolInfoInterface for PostgresInfo {
                 LogMessageType::Request => {
                     self.req_type = pg.req_type;
                     self.context = pg.context.clone();
+                    self.req_command = pg.req_command.clone();
+                    self.statement = pg.statement.clone();
+                    if !pg.database.is_empty() {
+                        self.database = pg.database.clone();
+                    }
+                    if !pg.user.is_empty() {
+                        self.user = pg.user.clone();
+                    }
+                    if pg.is_tls {
+                        self.is_tls = true;
+                    }
                 }
                 LogMessageType::Response => {
                     self.resp_type = pg.resp_type;
                     self.response_code = pg.response_code;
                     self.error_code = pg.error_code;
+                    self.sqlstate = pg.sqlstate;
                     self.error_message = pg.error_message;
+                    self.error_class = pg.error_class;
                     self.status = pg.status;
                     self.affected_rows = pg.affected_rows;
                 }
@@ -107,14 +144,36 @@ impl L7ProtocolInfoInterface for PostgresInfo {
             req_len: None,
             resp_len: None,
             req: L7Request {
-                req_type: String::from(char::from(self.req_type)),
-                domain: String::new(),
-                resource: self.context,
+                req_type: if self.req_command.is_empty() {
+                    String::from(char::from(self.req_type))
+                } else {
+                    self.req_command
+                },
+                // the database named by a StartupMessage (the user name when it
+                // names none); the user is shown as the resource only when the
+                // request carries neither SQL nor a statement name.
+                domain: self.database,
+                // An Execute that arrives in its own segment (prepared-statement
+                // reuse) carries no SQL, only the portal/statement name from the
+                // earlier Parse; surface that name so the resource is not empty.
+                resource: if !self.context.is_empty() {
+                    self.context
+                } else if !self.statement.is_empty() {
+                    self.statement
+                } else {
+                    self.user
+                },
             },
             resp: L7Response {
                 status: self.status,
                 code: Some(self.resp_type as i32),
-                result: self.error_message,
+                // prefix the message with the derived SQLSTATE class so errors can
+                // be grouped by class rather than by raw text.
+                result: match (self.error_class.is_empty(), self.error_message.is_empty()) {
+                    (true, _) => self.error_message,
+                    (false, true) => self.error_class,
+                    (false, false) => format!("{}: {}", self.error_class, self.error_message),
+                },
                 ..Default::default()
             },
             ext_info: Some(ExtendedInfo {
@@ -184,92 +243,302 @@ impl PostgresqlLog {
     payload: len - 4 byte
     */
 
+    /*
+    Request side. The simple-query message ('Q') carries the SQL directly, while
+    the extended-query flow spreads one logical query across several messages:
+        'P' Parse    destination statement name (cstring) + SQL (cstring) + param-type count
+        'B' Bind     portal name (cstring) + source statement name (cstring) + values
+        'D' Describe  kind byte + statement/portal name (cstring)
+        'E' Execute  portal name (cstring) + max rows (int32)
+    The SQL text only appears in Parse, so the statement/portal name is kept to
+    serve as the resource of a request that carries no SQL (e.g. a lone Execute).
+    session_id() does not use it.
+    */
+    // Record the leading command and an obfuscated template for the SQL so that
+    // aggregation groups on the query shape rather than on individual literals.
+    fn set_sql(&mut self, sql: &str) {
+        self.info.req_command = leading_command(sql);
+        self.info.context = obfuscate_sql(sql);
+    }
+
+    fn parse_request(&mut self, typ: u8, data: &[u8]) {
+        self.info.req_type = typ;
+        match char::from(typ) {
+            QUERY_SIMPLE_QUERY => {
+                let sql = cstring(data)
+                    .map(|(s, _)| s)
+                    .unwrap_or_else(|| String::from_utf8_lossy(data).to_string());
+                self.set_sql(&sql);
+            }
+            QUERY_PARSE => {
+                let mut d = data;
+                if let Some((name, rest)) = cstring(d) {
+                    self.info.statement = name;
+                    d = rest;
+                }
+                if let Some((sql, _)) = cstring(d) {
+                    self.set_sql(&sql);
+                }
+            }
+            QUERY_BIND => {
+                let mut d = data;
+                let portal = cstring(d);
+                if let Some((_, rest)) = &portal {
+                    d = rest;
+                }
+                // prefer the source statement name, falling back to the portal name
+                let statement = cstring(d).map(|(s, _)| s).filter(|s| !s.is_empty());
+                self.info.statement = statement
+                    .or_else(|| portal.map(|(s, _)| s).filter(|s| !s.is_empty()))
+                    .unwrap_or_default();
+            }
+            QUERY_DESCRIBE => {
+                // skip the leading kind byte ('S' statement / 'P' portal)
+                if let Some(d) = data.get(1..) {
+                    if let Some((name, _)) = cstring(d) {
+                        if !name.is_empty() {
+                            self.info.statement = name;
+                        }
+                    }
+                }
+            }
+            QUERY_EXEC => {
+                if let Some((portal, _)) = cstring(data) {
+                    if !portal.is_empty() {
+                        self.info.statement = portal;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /*
+    A single TCP segment routinely packs several framed messages (e.g.
+    Parse+Bind+Describe+Execute+Sync in one write, or RowDescription+several
+    DataRow+CommandComplete in one read). Walk the buffer frame by frame using
+    the length prefix, accumulating state across them: the SQL is taken from the
+    first request-bearing message and DataRow ('D') responses are counted so the
+    returned-row count of a SELECT is known even before CommandComplete. A
+    trailing frame whose length exceeds the remaining bytes is treated as a
+    truncated capture and stops the walk cleanly rather than erroring.
+    */
+    /*
+    The first client messages have no type byte: a 4-byte length is followed by
+    a 4-byte protocol version (StartupMessage) or request code (SSLRequest,
+    GSSENCRequest, CancelRequest). Handle them before the framed-message walk.
+    Returns true when the buffer was a typeless front-door message.
+    */
+    fn parse_startup(&mut self, payload: &[u8]) -> bool {
+        if self.info.msg_type != LogMessageType::Request || payload.len() < 8 {
+            return false;
+        }
+        let code = read_u32_be(&payload[4..8]);
+        match code {
+            SSL_REQUEST_CODE => {
+                self.info.is_tls = true;
+                self.info.req_command = String::from("SSLREQUEST");
+                true
+            }
+            GSSENC_REQUEST_CODE => {
+                self.info.req_command = String::from("GSSENCREQUEST");
+                true
+            }
+            CANCEL_REQUEST_CODE => {
+                self.info.req_command = String::from("CANCEL");
+                true
+            }
+            STARTUP_PROTOCOL_VERSION => {
+                self.info.req_command = String::from("CONNECT");
+                // NUL-delimited key/value pairs, terminated by an empty key.
+                let mut data = &payload[8..];
+                while let Some((key, rest)) = cstring(data) {
+                    if key.is_empty() {
+                        break;
+                    }
+                    let Some((value, rest)) = cstring(rest) else {
+                        break;
+                    };
+                    match key.as_str() {
+                        "user" => self.info.user = value,
+                        "database" => self.info.database = value,
+                        _ => {}
+                    }
+                    data = rest;
+                }
+                // StartupMessage omits "database" when it equals the user name.
+                if self.info.database.is_empty() {
+                    self.info.database = self.info.user.clone();
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
     fn parse(&mut self, payload: &[u8]) -> Result<()> {
+        if self.parse_startup(payload) {
+            return Ok(());
+        }
         if payload.len() < 5 {
             return Err(Error::L7ProtocolUnknown);
         }
-        let typ = payload[0];
-        if !check_type(self.info.msg_type, typ) {
-            return Err(Error::L7ProtocolUnknown);
-        };
 
-        let data_len = read_u32_be(&payload[1..5]);
-        if payload.len() - 1 < data_len as usize {
+        let mut data = payload;
+        let mut parsed_any = false;
+        let mut data_rows: u64 = 0;
+        while data.len() >= 5 {
+            let typ = data[0];
+            // The first frame identifies the protocol, so gate it on the messages
+            // we actually handle to avoid misreading arbitrary TCP payloads as
+            // PostgreSQL; interior frames may be any well-framed message type so
+            // the walk can skip the ones it does not interpret.
+            let recognized = if parsed_any {
+                check_type(self.info.msg_type, typ)
+            } else {
+                first_frame_type(self.info.msg_type, typ)
+            };
+            if !recognized {
+                if parsed_any {
+                    break;
+                }
+                return Err(Error::L7ProtocolUnknown);
+            }
+
+            // the length prefix counts itself (4 bytes) plus the payload, but not
+            // the leading type byte.
+            let data_len = read_u32_be(&data[1..5]) as usize;
+            if data_len < 4 {
+                // garbage length: stop cleanly once we already have state, only
+                // reject outright when this is the very first frame.
+                if parsed_any {
+                    break;
+                }
+                return Err(Error::L7ProtocolUnknown);
+            }
+            let frame_len = data_len + 1;
+            if data.len() < frame_len {
+                // truncated trailing frame: stop on what we have.
+                break;
+            }
+            let body = &data[5..frame_len];
+
+            match self.info.msg_type {
+                LogMessageType::Request => {
+                    // keep the SQL from the first request-bearing message.
+                    if self.info.context.is_empty() {
+                        self.parse_request(typ, body);
+                    }
+                }
+                LogMessageType::Response => self.parse_response(typ, body, &mut data_rows),
+                _ => {}
+            }
+
+            parsed_any = true;
+            data = &data[frame_len..];
+        }
+
+        if !parsed_any {
             return Err(Error::L7ProtocolUnknown);
         }
 
-        match self.info.msg_type {
-            LogMessageType::Request => {
-                self.info.req_type = typ;
-                self.info.context = String::from_utf8_lossy(&payload[5..]).to_string();
-            }
-            LogMessageType::Response => {
-                self.info.resp_type = typ;
-                match char::from(self.info.resp_type) {
-                    RESP_ERROR => {
-                        self.info.status = L7ResponseStatus::Error;
-                        /*
-                        type: 1B
-                        len: 4B
-                        Severity: string, end with 0x0
-                        Text: string end with 0x0
-                        code: string end with 0x0
-
-                        ...
-
-                        */
-                        let mut data = &payload[5..];
-
-                        for _ in 0..2 {
-                            if let Some(idx) = data.iter().position(|x| *x == 0) {
-                                data = &data[idx + 1..];
-                            } else {
-                                return Ok(());
-                            }
-                        }
-                        if let Some(idx) = data.iter().position(|x| *x == 0) {
-                            self.info.error_message =
-                                String::from_utf8_lossy(&data[..idx]).to_string();
-                        }
+        // CommandComplete reports a SELECT as "SELECT n"; if it was missing from
+        // this buffer fall back to the number of DataRow messages we saw.
+        if self.info.affected_rows == 0 && data_rows > 0 {
+            self.info.affected_rows = data_rows;
+        }
+
+        return Ok(());
+    }
 
-                        return Ok(());
+    fn parse_response(&mut self, typ: u8, body: &[u8], data_rows: &mut u64) {
+        self.info.resp_type = typ;
+        match char::from(typ) {
+            RESP_ERROR => {
+                self.info.status = L7ResponseStatus::Error;
+                /*
+                The ErrorResponse / NoticeResponse body is a sequence of
+                fields, each a one-byte type code followed by a NUL-terminated
+                value, terminated by a zero byte. Field order is not fixed:
+                    'S'/'V' severity   'C' SQLSTATE code   'M' primary message
+                    'D' detail   'H' hint   'P' position   ...
+                */
+                let mut data = body;
+                while let Some((&field, rest)) = data.split_first() {
+                    if field == 0 {
+                        break;
                     }
-                    RESP_COMM_COMPLETE => {
-                        self.info.status = L7ResponseStatus::Ok;
-                        // INSERT xxx xxx0x0 where last xxx is row effect.
-                        // DELETE xxx0x0
-                        // UPDATE xxx0x0
-                        let mut tag = &payload[5..];
-                        if let Some(idx) = tag.iter().position(|x| *x == 0x20) {
-                            let op = &tag[..idx];
-                            tag = &tag[idx + 1..];
-                            if op.eq("INSERT".as_bytes()) {
-                                if let Some(idx) = tag.iter().position(|x| *x == 0x20) {
-                                    tag = &tag[idx + 1..];
+                    let Some((value, rest)) = cstring(rest) else {
+                        break;
+                    };
+                    match char::from(field) {
+                        ERR_FIELD_MESSAGE => self.info.error_message = value,
+                        ERR_FIELD_CODE => {
+                            // SQLSTATE is five characters; the first two identify the
+                            // class. Always keep the full code; additionally record
+                            // the numeric class in error_code when it has no letters,
+                            // plus a human-readable class name for dashboards. Slice
+                            // on bytes so a malformed (non-ASCII) value cannot panic
+                            // on a char boundary.
+                            if let Some(class) = value.as_bytes().get(..2) {
+                                let class = String::from_utf8_lossy(class);
+                                self.info.error_code = class.parse::<i32>().ok();
+                                let name = sqlstate_class(class.as_ref());
+                                self.info.error_class = if name.is_empty() {
+                                    class.to_string()
                                 } else {
-                                    return Ok(());
-                                }
-                            } else {
-                                if !(op.eq("DELETE".as_bytes()) || op.eq("UPDATE".as_bytes())) {
-                                    return Ok(());
-                                }
+                                    name.to_string()
+                                };
                             }
+                            self.info.sqlstate = value;
                         }
-
-                        if let Some(idx) = tag.iter().position(|x| *x == 0x0) {
-                            let row_eff = String::from_utf8_lossy(&tag[..idx]).to_string();
-                            self.info.affected_rows = row_eff.parse().unwrap_or(0);
-                        }
+                        _ => {}
                     }
-                    _ => {
-                        self.info.status = L7ResponseStatus::Ok;
+                    data = rest;
+                }
+            }
+            RESP_DATA_ROW => {
+                self.info.status = L7ResponseStatus::Ok;
+                *data_rows += 1;
+            }
+            RESP_COMM_COMPLETE => {
+                self.info.status = L7ResponseStatus::Ok;
+                // INSERT xxx xxx0x0 where last xxx is row effect.
+                // DELETE xxx0x0
+                // UPDATE xxx0x0
+                // SELECT xxx0x0
+                let mut tag = body;
+                if let Some(idx) = tag.iter().position(|x| *x == 0x20) {
+                    let op = &tag[..idx];
+                    tag = &tag[idx + 1..];
+                    if op.eq("INSERT".as_bytes()) {
+                        if let Some(idx) = tag.iter().position(|x| *x == 0x20) {
+                            tag = &tag[idx + 1..];
+                        } else {
+                            return;
+                        }
+                    } else if !(op.eq("DELETE".as_bytes())
+                        || op.eq("UPDATE".as_bytes())
+                        || op.eq("SELECT".as_bytes()))
+                    {
+                        return;
                     }
                 }
+
+                if let Some(idx) = tag.iter().position(|x| *x == 0x0) {
+                    let row_eff = String::from_utf8_lossy(&tag[..idx]).to_string();
+                    self.info.affected_rows = row_eff.parse().unwrap_or(0);
+                }
+            }
+            _ => {
+                // A segment may carry several framed replies; do not let a later
+                // housekeeping message (ReadyForQuery, ParameterStatus, ...)
+                // downgrade a failure already recorded from an ErrorResponse.
+                if !matches!(self.info.status, L7ResponseStatus::Error) {
+                    self.info.status = L7ResponseStatus::Ok;
+                }
             }
-            _ => {}
         }
-
-        return Ok(());
     }
 }
 
@@ -311,24 +580,249 @@ case 'c':        Copy Done
 case 'R':        Authentication Reques
 */
 const QUERY_SIMPLE_QUERY: char = 'Q';
+const QUERY_PARSE: char = 'P';
+const QUERY_BIND: char = 'B';
+const QUERY_DESCRIBE: char = 'D';
 const QUERY_EXEC: char = 'E';
 
+// Typeless front-door messages: a 4-byte length followed by these 4-byte codes.
+const STARTUP_PROTOCOL_VERSION: u32 = 196608; // 3.0
+const SSL_REQUEST_CODE: u32 = 80877103;
+const GSSENC_REQUEST_CODE: u32 = 80877104;
+const CANCEL_REQUEST_CODE: u32 = 80877102;
+
 const RESP_ERROR: char = 'E';
+
+// ErrorResponse / NoticeResponse field-type codes.
+const ERR_FIELD_CODE: char = 'C';
+const ERR_FIELD_MESSAGE: char = 'M';
 const RESP_COMM_COMPLETE: char = 'C';
-const RESP_ROW_DESC: char = 'T';
 const RESP_DATA_ROW: char = 'D';
 
+// Read a NUL-terminated string, returning the decoded value and the bytes
+// following the terminator. Returns None when no terminator is present.
+fn cstring(data: &[u8]) -> Option<(String, &[u8])> {
+    let idx = data.iter().position(|x| *x == 0)?;
+    let s = String::from_utf8_lossy(&data[..idx]).to_string();
+    return Some((s, &data[idx + 1..]));
+}
+
+// Map a two-character SQLSTATE class to a human-readable name. Only the classes
+// that are useful to group on are spelled out; anything else maps to an empty
+// string and the caller reports it by its raw two-character class so dashboards
+// still have a stable key.
+fn sqlstate_class(class: &str) -> &'static str {
+    match class {
+        "00" => "successful completion",
+        "01" => "warning",
+        "02" => "no data",
+        "08" => "connection exception",
+        "22" => "data exception",
+        "23" => "integrity constraint violation",
+        "25" => "invalid transaction state",
+        "28" => "invalid authorization specification",
+        "40" => "transaction rollback",
+        "42" => "syntax error or access rule violation",
+        "53" => "insufficient resources",
+        "54" => "program limit exceeded",
+        "57" => "operator intervention",
+        "58" => "system error",
+        "XX" => "internal error",
+        _ => "",
+    }
+}
+
+// Extract the leading command keyword, upper-cased, e.g. "SELECT". Leading
+// whitespace, line (`-- ...`) and block (`/* ... */`) comments and opening
+// parentheses are skipped first so parenthesized or comment-prefixed queries
+// still resolve to their command.
+fn leading_command(sql: &str) -> String {
+    let mut s = sql.trim_start();
+    loop {
+        if let Some(rest) = s.strip_prefix("--") {
+            s = rest.split_once('\n').map(|(_, r)| r).unwrap_or("");
+        } else if let Some(rest) = s.strip_prefix("/*") {
+            s = rest.split_once("*/").map(|(_, r)| r).unwrap_or("");
+        } else if let Some(rest) = s.strip_prefix('(') {
+            s = rest;
+        } else {
+            let trimmed = s.trim_start();
+            if trimmed.len() == s.len() {
+                break;
+            }
+            s = trimmed;
+        }
+    }
+    s.split(|c: char| !c.is_ascii_alphabetic())
+        .next()
+        .unwrap_or("")
+        .to_uppercase()
+}
+
+fn is_ident_byte(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'_'
+}
+
+// Replace string literals, numeric literals and `IN (...)` lists with `?`
+// placeholders while preserving identifiers and structure, so that captured
+// query text has stable low-cardinality grouping keys and leaks no literals.
+fn obfuscate_sql(sql: &str) -> String {
+    let b = sql.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(b.len());
+    let mut i = 0;
+    // the two previous original bytes, used to recognise the `E'...'` prefix
+    // (prev == 'E'/'e', prev2 not an identifier byte).
+    let mut prev = 0u8;
+    let mut prev2 = 0u8;
+    while i < b.len() {
+        let c = b[i];
+        if c == b'\'' {
+            // single-quoted string literal. '' is always an escaped quote; a
+            // backslash escapes the following byte only in an E'...' string
+            // (standard strings treat backslash literally).
+            let escapes = (prev == b'E' || prev == b'e') && !is_ident_byte(prev2);
+            out.push(b'?');
+            i += 1;
+            while i < b.len() {
+                if escapes && b[i] == b'\\' {
+                    i += 2;
+                    continue;
+                }
+                if b[i] == b'\'' {
+                    if i + 1 < b.len() && b[i + 1] == b'\'' {
+                        i += 2;
+                        continue;
+                    }
+                    i += 1;
+                    break;
+                }
+                i += 1;
+            }
+            prev2 = prev;
+            prev = b'?';
+            continue;
+        }
+        if c == b'$' && i + 1 < b.len() && !b[i + 1].is_ascii_digit() {
+            // dollar-quoted string literal $tag$...$tag$ (the tag is an optional
+            // identifier). `$1` positional parameters fall through to be copied.
+            let mut k = i + 1;
+            while k < b.len() && is_ident_byte(b[k]) {
+                k += 1;
+            }
+            if k < b.len() && b[k] == b'$' {
+                let delim = &b[i..=k];
+                let mut j = k + 1;
+                let end = loop {
+                    if j + delim.len() > b.len() {
+                        break b.len(); // unterminated literal: consume the rest
+                    }
+                    if &b[j..j + delim.len()] == delim {
+                        break j + delim.len();
+                    }
+                    j += 1;
+                };
+                out.push(b'?');
+                i = end;
+                prev2 = prev;
+                prev = b'?';
+                continue;
+            }
+        }
+        if c.is_ascii_digit() && !is_ident_byte(prev) && prev != b'$' {
+            // numeric literal (int, decimal or exponent), not a digit inside an
+            // identifier such as `col1`.
+            out.push(b'?');
+            while i < b.len()
+                && (b[i].is_ascii_digit() || b[i] == b'.' || b[i] == b'e' || b[i] == b'E')
+            {
+                i += 1;
+            }
+            prev2 = prev;
+            prev = b'?';
+            continue;
+        }
+        out.push(c);
+        prev2 = prev;
+        prev = c;
+        i += 1;
+    }
+    let template = String::from_utf8_lossy(&out).to_string();
+    return collapse_in_lists(&template);
+}
+
+// Collapse an `IN (?, ?, ...)` placeholder list down to `IN (?)` so that lists
+// of different lengths share one template.
+fn collapse_in_lists(s: &str) -> String {
+    let b = s.as_bytes();
+    let mut out: Vec<u8> = Vec::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        let is_in = (b[i] == b'i' || b[i] == b'I')
+            && i + 1 < b.len()
+            && (b[i + 1] == b'n' || b[i + 1] == b'N')
+            && (i == 0 || !is_ident_byte(b[i - 1]))
+            && (i + 2 >= b.len() || !is_ident_byte(b[i + 2]));
+        if is_in {
+            let mut j = i + 2;
+            while j < b.len() && b[j] == b' ' {
+                j += 1;
+            }
+            if j < b.len() && b[j] == b'(' {
+                if let Some(rel) = s[j..].find(')') {
+                    let close = j + rel;
+                    let inner = s[j + 1..close].trim();
+                    if !inner.is_empty() && inner.split(',').all(|p| p.trim() == "?") {
+                        out.extend_from_slice(b"IN (?)");
+                        i = close + 1;
+                        continue;
+                    }
+                }
+            }
+        }
+        out.push(b[i]);
+        i += 1;
+    }
+    return String::from_utf8_lossy(&out).to_string();
+}
+
+// Whether `typ` is a message type we extract information from, or a
+// RowDescription 'T', which opens every row-returning reply. Used to gate the
+// first frame of a buffer so that protocol identification does not match an
+// arbitrary TCP payload whose leading byte happens to be a valid-but-unhandled
+// message type (e.g. a bare Sync 'S' or Flush 'H'). The typeless StartupMessage
+// / SSLRequest path is recognized separately in parse_startup.
+fn first_frame_type(msg_type: LogMessageType, typ: u8) -> bool {
+    let c = char::from(typ);
+    match msg_type {
+        LogMessageType::Request => return matches!(c, 'Q' | 'P' | 'B' | 'E'),
+        LogMessageType::Response => {
+            return matches!(c, RESP_ERROR | RESP_COMM_COMPLETE | RESP_DATA_ROW | 'T')
+        }
+        _ => return false,
+    }
+}
+
+// Whether `typ` is a valid message-type byte for this direction. This is the
+// full wire-format set, not just the messages we extract from, so the frame
+// walk can use the length prefix to advance past messages it does not interpret
+// (e.g. ParseComplete '1' / BindComplete '2' / ReadyForQuery 'Z') instead of
+// rejecting the whole segment.
 fn check_type(msg_type: LogMessageType, typ: u8) -> bool {
     let c = char::from(typ);
     match msg_type {
-        LogMessageType::Request => match c {
-            QUERY_SIMPLE_QUERY | QUERY_EXEC => return true,
-            _ => return false,
-        },
-        LogMessageType::Response => match c {
-            RESP_ERROR | RESP_COMM_COMPLETE | RESP_ROW_DESC | RESP_DATA_ROW => return true,
-            _ => return false,
-        },
+        LogMessageType::Request => {
+            return matches!(
+                c,
+                'Q' | 'P' | 'B' | 'E' | 'F' | 'C' | 'D' | 'H' | 'S' | 'X' | 'd' | 'c' | 'f' | 'p'
+            )
+        }
+        LogMessageType::Response => {
+            return matches!(
+                c,
+                'C' | 'E' | 'Z' | 'I' | '1' | '2' | '3' | 'S' | 'K' | 'T' | 'n' | 't' | 'D' | 'G'
+                    | 'H' | 'W' | 'd' | 'c' | 'R' | 'N' | 'A' | 's' | 'V' | 'v'
+            )
+        }
         _ => return false,
     }
 }
\ No newline at end of file